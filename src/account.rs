@@ -1,3 +1,4 @@
+use crate::error::LedgerError;
 use crate::io::{InputLine, InputLineType};
 use crate::transaction::Transaction;
 use crate::{ClientId, TransactionId};
@@ -17,14 +18,36 @@ pub struct AccountStatus {
     pub locked: bool,
 }
 
+impl AccountStatus {
+    /// Round `available` and `held` to `scale` fractional digits and derive
+    /// `total` from the rounded values, so output is rendered consistently
+    /// instead of at whatever scale the underlying arithmetic happened to
+    /// accumulate -- and so the rendered row always satisfies
+    /// `total == available + held`, which rounding each field independently
+    /// can't guarantee.
+    pub fn rounded(self, scale: u32) -> Self {
+        let mut available = self.available.round_dp(scale);
+        let mut held = self.held.round_dp(scale);
+        // `round_dp` never adds digits, so pad shorter values up to `scale`.
+        available.rescale(scale);
+        held.rescale(scale);
+        Self {
+            available,
+            held,
+            total: available + held,
+            ..self
+        }
+    }
+}
+
 pub struct Account {
     available: Decimal,
     held: Decimal,
     locked: bool,
 
-    /// Collection of disputable transactions. Using a hashmap as we always refer to
-    /// a transaction by its id. Currently disputes are implemented for deposits.
-    deposits: HashMap<TransactionId, Transaction>,
+    /// Collection of disputable transactions (deposits and withdrawals). Using a
+    /// hashmap as we always refer to a transaction by its id.
+    transactions: HashMap<TransactionId, Transaction>,
 }
 
 impl Default for Account {
@@ -33,22 +56,24 @@ impl Default for Account {
             available: Decimal::new(0, 0),
             held: Decimal::new(0, 0),
             locked: false,
-            deposits: HashMap::new(),
+            transactions: HashMap::new(),
         }
     }
 }
 
 impl Account {
     /// Process an account operation.
-    pub fn process(&mut self, input_line: InputLine) {
-        if !self.locked {
-            match input_line.r#type {
-                InputLineType::Deposit => self.deposit(input_line),
-                InputLineType::Withdrawal => self.withdrawal(input_line),
-                InputLineType::Dispute => self.dispute(input_line),
-                InputLineType::Resolve => self.resolve(input_line),
-                InputLineType::Chargeback => self.chargeback(input_line),
-            };
+    pub fn process(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
+        if self.locked {
+            return Err(LedgerError::FrozenAccount);
+        }
+
+        match input_line.r#type {
+            InputLineType::Deposit => self.deposit(input_line),
+            InputLineType::Withdrawal => self.withdrawal(input_line),
+            InputLineType::Dispute => self.dispute(input_line),
+            InputLineType::Resolve => self.resolve(input_line),
+            InputLineType::Chargeback => self.chargeback(input_line),
         }
     }
 
@@ -62,60 +87,85 @@ impl Account {
         }
     }
 
-    fn deposit(&mut self, input_line: InputLine) {
+    fn deposit(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
         // The unwrap is safe because the Engine validated the input before sending the transaction here.
         let amount = input_line.amount.unwrap();
-        // Just increase the available amount and add it the to the deposits history.
+        // Just increase the available amount and add it the to the transaction history.
         self.available += amount;
-        self.deposits
+        self.transactions
             .insert(input_line.id, Transaction::deposit(amount));
+        Ok(())
     }
 
-    fn withdrawal(&mut self, input_line: InputLine) {
+    fn withdrawal(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
         // The unwrap is safe because the Engine validated the input before sending the transaction here.
         let amount = input_line.amount.unwrap();
 
-        // If we don't have enough money, just ingore.
-        if self.available >= amount {
-            self.available -= amount;
+        if self.available < amount {
+            return Err(LedgerError::NotEnoughFunds);
         }
+
+        self.available -= amount;
+        self.transactions
+            .insert(input_line.id, Transaction::withdrawal(amount));
+        Ok(())
     }
 
-    fn dispute(&mut self, input_line: InputLine) {
-        // Disputes only work for deposits.
-        if let Some(transaction) = self.deposits.get_mut(&input_line.id) {
-            // Check that it's not already disputed.
-            if !transaction.disputed() {
-                // Mark this deposit as disputed, so that we can validate that resolves and
-                // chargebacks are only applied on disputes.
-                transaction.dispute();
-                let amount = transaction.amount();
-                // The client may end up with a negative balance if they already withdrew the money.
-                self.available -= amount;
-                self.held += amount;
+    fn dispute(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
+        let transaction = self.transactions.get_mut(&input_line.id).ok_or({
+            LedgerError::UnknownTransaction {
+                client: input_line.client,
+                tx: input_line.id,
             }
+        })?;
+
+        // The transition itself checks that we're coming from `Processed`, so a
+        // transaction that's already disputed (or further along) is rejected here.
+        if !transaction.dispute() {
+            return Err(LedgerError::AlreadyDisputed);
         }
+
+        let amount = transaction.signed_amount();
+        // Deposits move available -> held; withdrawals move the other way, which
+        // may send held negative as we temporarily roll the withdrawal back.
+        self.available -= amount;
+        self.held += amount;
+        Ok(())
     }
 
-    fn resolve(&mut self, input_line: InputLine) {
-        if let Some(transaction) = self.deposits.get_mut(&input_line.id) {
-            if transaction.disputed() {
-                self.available += transaction.amount();
-                self.held -= transaction.amount();
-                transaction.undispute();
+    fn resolve(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
+        let transaction = self.transactions.get_mut(&input_line.id).ok_or({
+            LedgerError::UnknownTransaction {
+                client: input_line.client,
+                tx: input_line.id,
             }
+        })?;
+
+        if !transaction.resolve() {
+            return Err(LedgerError::NotDisputed);
         }
-    }
 
-    fn chargeback(&mut self, input_line: InputLine) {
-        if let Some(transaction) = self.deposits.get_mut(&input_line.id) {
-            if transaction.disputed() {
-                self.held -= transaction.amount();
-                transaction.undispute();
+        let amount = transaction.signed_amount();
+        self.available += amount;
+        self.held -= amount;
+        Ok(())
+    }
 
-                self.lock();
+    fn chargeback(&mut self, input_line: InputLine) -> Result<(), LedgerError> {
+        let transaction = self.transactions.get_mut(&input_line.id).ok_or({
+            LedgerError::UnknownTransaction {
+                client: input_line.client,
+                tx: input_line.id,
             }
+        })?;
+
+        if !transaction.chargeback() {
+            return Err(LedgerError::NotDisputed);
         }
+
+        self.held -= transaction.signed_amount();
+        self.lock();
+        Ok(())
     }
 
     // Don't store the total, we can calculate it when needed
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+use crate::{ClientId, TransactionId};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+/// Everything that can go wrong while applying a single transaction to an account.
+/// These are never fatal to the overall run: the engine skips the offending
+/// transaction and moves on, optionally recording the error (see `Engine::strict`).
+pub enum LedgerError {
+    #[error("not enough available funds")]
+    NotEnoughFunds,
+
+    #[error("transaction {tx} for client {client} is unknown")]
+    UnknownTransaction { client: ClientId, tx: TransactionId },
+
+    #[error("transaction is already disputed")]
+    AlreadyDisputed,
+
+    #[error("transaction is not currently disputed")]
+    NotDisputed,
+
+    #[error("account is frozen")]
+    FrozenAccount,
+
+    #[error("record is malformed for its transaction type")]
+    MalformedRecord,
+}
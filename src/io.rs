@@ -1,3 +1,4 @@
+use crate::error::LedgerError;
 use crate::{ClientId, TransactionId};
 use rust_decimal::Decimal;
 use serde::Deserialize;
@@ -24,13 +25,16 @@ pub struct InputLine {
 }
 
 impl InputLine {
-    pub fn valid(&self) -> bool {
-        match self.r#type {
-            InputLineType::Deposit => self.amount.is_some(),
-            InputLineType::Withdrawal => self.amount.is_some(),
-            InputLineType::Dispute => self.amount.is_none(),
-            InputLineType::Resolve => self.amount.is_none(),
-            InputLineType::Chargeback => self.amount.is_none(),
+    pub fn valid(&self) -> Result<(), LedgerError> {
+        let amount_expected = matches!(
+            self.r#type,
+            InputLineType::Deposit | InputLineType::Withdrawal
+        );
+
+        if self.amount.is_some() == amount_expected {
+            Ok(())
+        } else {
+            Err(LedgerError::MalformedRecord)
         }
     }
 }
@@ -1,15 +1,22 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
 use std::env;
 use std::fs::File;
+use std::sync::{Arc, Mutex};
 
 use csv::{Error as CsvError, Trim};
 
 mod account;
+mod error;
 mod io;
+mod parallel;
+mod server;
+mod source;
 mod transaction;
 
 use account::Account;
+use error::LedgerError;
 use io::{InputLine, InputLineType};
+use source::{CsvSource, TransactionSource};
 
 /// Useful type alias for client id.
 type ClientId = u16;
@@ -19,77 +26,244 @@ type TransactionId = u32;
 /// If more error types are to be added, we should define a new Error enum.
 type Result<T> = std::result::Result<T, CsvError>;
 
+/// Number of fractional digits `write_status_to_csv` rounds amounts to when
+/// no explicit scale is requested, matching the spec's 4-decimal-place domain.
+const DEFAULT_STATUS_SCALE: u32 = 4;
+
 #[derive(Default)]
 /// The transaction engine type.
-/// Uses the `newtype` design pattern, since it only has one field and
-/// we want to be able to have methods defined on it.
-struct Engine(HashMap<ClientId, Account>);
+pub(crate) struct Engine {
+    accounts: HashMap<ClientId, Account>,
+    /// When true, every rejected transaction is recorded in `errors` instead of
+    /// being silently skipped. The happy path is unaffected either way.
+    strict: bool,
+    /// Rejected transactions collected as `(line_number, error)`. Only populated
+    /// when the engine was built with `Engine::strict`.
+    errors: Vec<(usize, LedgerError)>,
+    /// Number of worker threads to shard account processing across. `0` (the
+    /// default) or `1` keeps the single-threaded path.
+    workers: usize,
+}
 
 impl Engine {
+    /// Turn on strict mode: every rejected transaction is recorded in `errors`
+    /// instead of being silently skipped.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Shard account processing across `workers` threads, partitioned by
+    /// `ClientId % workers` (see the `parallel` module). A single client's
+    /// transactions always land on the same worker and are applied in the order
+    /// they were read; ordering between different clients is not preserved.
+    /// Only `process_from_csv` shards; `process_from_source` and `process_one`
+    /// always apply transactions on the calling thread.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
     /// Process transactions in CSV format, in-order.
     pub fn process_from_csv<R: std::io::Read>(&mut self, reader: R) -> Result<()> {
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .trim(Trim::All)
-            .from_reader(reader);
+        if self.workers > 1 {
+            let mut csv_reader = csv::ReaderBuilder::new()
+                .has_headers(true)
+                .trim(Trim::All)
+                .from_reader(reader);
+
+            // Data rows start on line 2: line 1 is the header.
+            let input_lines = csv_reader
+                .deserialize::<InputLine>()
+                .enumerate()
+                .map(|(line, result)| result.map(|input_line| (line + 2, input_line)));
+
+            // On a read error partway through, still keep whatever was merged
+            // from the worker shards, matching `process_from_source`'s
+            // sequential behavior of leaving `self` with everything applied so far.
+            match parallel::process(input_lines, self.workers, self.strict) {
+                Ok((accounts, errors)) => {
+                    self.accounts = accounts;
+                    self.errors = errors;
+                    Ok(())
+                }
+                Err((error, (accounts, errors))) => {
+                    self.accounts = accounts;
+                    self.errors = errors;
+                    Err(error)
+                }
+            }
+        } else {
+            self.process_from_source(CsvSource::new(reader))
+        }
+    }
 
-        for result in reader.deserialize::<InputLine>() {
-            self.process_transaction(result?);
+    /// Process every transaction yielded by `source`, in order, regardless of
+    /// the wire format it was read from -- see the `source` module for the CSV
+    /// and newline-delimited-JSON implementations.
+    pub fn process_from_source<S: TransactionSource>(
+        &mut self,
+        mut source: S,
+    ) -> std::result::Result<(), S::Error> {
+        while let Some(result) = source.next_transaction() {
+            let (line, input_line) = result?;
+            self.process_one(line, input_line);
         }
 
         Ok(())
     }
 
-    /// Write the status of every account in CSV format.
-    pub fn write_status_to_csv<W: std::io::Write>(&self, writer: W) -> Result<()> {
+    /// Apply a single already-decoded transaction, recording it under `line` in
+    /// `errors` if it's rejected and strict mode is on. Split out from
+    /// `process_from_source` so a caller can decode a transaction (e.g. a socket
+    /// read) without holding onto the engine the whole time -- see `server::serve`.
+    pub(crate) fn process_one(&mut self, line: usize, input_line: InputLine) {
+        if let Err(error) = self.process_transaction(input_line) {
+            if self.strict {
+                self.errors.push((line, error));
+            }
+        }
+    }
+
+    /// Rejected transactions collected so far, as `(line_number, error)`. Only
+    /// populated in strict mode.
+    pub fn errors(&self) -> &[(usize, LedgerError)] {
+        &self.errors
+    }
+
+    /// Write the status of every account in CSV format, ordered by ascending
+    /// `ClientId` so output is deterministic across runs, with every decimal
+    /// field rounded to `scale` fractional digits.
+    pub fn write_status_to_csv<W: std::io::Write>(&self, writer: W, scale: u32) -> Result<()> {
         let mut writer = csv::WriterBuilder::new().from_writer(writer);
 
-        for (client_id, account) in self.0.iter() {
-            writer.serialize(account.get_status(*client_id))?;
+        let sorted: BTreeMap<_, _> = self.accounts.iter().collect();
+        for (client_id, account) in sorted {
+            writer.serialize(account.get_status(*client_id).rounded(scale))?;
         }
 
         Ok(())
     }
 
-    /// Main function for transaction processing. Can be used for data expressed in multiple
-    /// formats. For example, it may be called in the future for processing a transaction
-    /// formatted as JSON received over a TCP socket.
-    fn process_transaction(&mut self, input_line: InputLine) {
-        if !input_line.valid() {
-            return;
+    /// Main function for transaction processing, used by every single-threaded
+    /// ingestion path (CSV batch, or a `TransactionSource` like JSON over a TCP
+    /// socket). The sharded path calls `apply_transaction` directly.
+    fn process_transaction(&mut self, input_line: InputLine) -> std::result::Result<(), LedgerError> {
+        apply_transaction(&mut self.accounts, input_line)
+    }
+}
+
+/// Apply a single input line to the right account in `accounts`, creating a new
+/// one on a first-seen deposit. Shared between the single-threaded and
+/// sharded-parallel processing paths (see the `parallel` module).
+pub(crate) fn apply_transaction(
+    accounts: &mut HashMap<ClientId, Account>,
+    input_line: InputLine,
+) -> std::result::Result<(), LedgerError> {
+    input_line.valid()?;
+
+    let entry = accounts.entry(input_line.client);
+
+    let account = match entry {
+        Entry::Occupied(e) => e.into_mut(),
+        Entry::Vacant(e) if input_line.r#type == InputLineType::Deposit => {
+            e.insert(Default::default())
         }
-        let entry = self.0.entry(input_line.client);
+        // A non-deposit referencing a client we've never seen can't be valid.
+        Entry::Vacant(_) => {
+            return Err(LedgerError::UnknownTransaction {
+                client: input_line.client,
+                tx: input_line.id,
+            })
+        }
+    };
 
-        let account = match entry {
-            Entry::Occupied(e) => e.into_mut(),
-            Entry::Vacant(e) if input_line.r#type == InputLineType::Deposit => {
-                e.insert(Default::default())
-            }
-            // If we got a Vacant entry but the tx type is not Deposit, ignore.
-            _ => return,
-        };
+    account.process(input_line)
+}
 
-        account.process(input_line);
+/// Build an engine honoring the `--strict`/`--workers` CLI flags.
+fn build_engine(strict: bool, workers: usize) -> Engine {
+    let mut engine = Engine::default();
+    if strict {
+        engine = engine.strict();
     }
+    if workers > 1 {
+        engine = engine.with_workers(workers);
+    }
+    engine
 }
 
-fn run_engine<R: std::io::Read, W: std::io::Write>(reader: R, writer: W) -> Result<()> {
-    let mut engine: Engine = Default::default();
+fn run_engine<R: std::io::Read, W: std::io::Write>(
+    reader: R,
+    writer: W,
+    strict: bool,
+    workers: usize,
+) -> Result<()> {
+    let mut engine = build_engine(strict, workers);
+
     engine.process_from_csv(reader)?;
 
-    engine.write_status_to_csv(writer)?;
+    if strict {
+        for (line, error) in engine.errors() {
+            eprintln!("line {line}: {error}");
+        }
+    }
+
+    engine.write_status_to_csv(writer, DEFAULT_STATUS_SCALE)?;
 
     Ok(())
 }
 
+/// Pull an optional `--workers <n>` flag and its value out of `args`, in place.
+fn take_workers_flag(args: &mut Vec<String>) -> usize {
+    match args.iter().position(|arg| arg == "--workers") {
+        Some(pos) => {
+            args.remove(pos);
+            args.remove(pos)
+                .parse()
+                .expect("--workers expects a positive integer")
+        }
+        None => 1,
+    }
+}
+
+/// Pull an optional `--listen <addr>` flag and its value out of `args`, in place.
+fn take_listen_flag(args: &mut Vec<String>) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == "--listen")?;
+    args.remove(pos);
+    Some(args.remove(pos))
+}
+
 fn main() {
     let mut args: Vec<String> = env::args().collect();
-    // We assume we get only one argument, the input file.
+    // An optional `--strict` flag turns on rejected-transaction reporting to stderr.
+    // With `--listen`, rejected transactions are collected but not printed.
+    let strict = args.iter().any(|arg| arg == "--strict");
+    args.retain(|arg| arg != "--strict");
+    let workers = take_workers_flag(&mut args);
+    let listen_addr = take_listen_flag(&mut args);
+
+    if let Some(addr) = listen_addr {
+        // In server mode there's no input file: transactions arrive over the
+        // accepted connections instead (see the `server` module).
+        assert_eq!(args.len(), 1);
+        let engine = Arc::new(Mutex::new(build_engine(strict, workers)));
+        server::serve(addr, engine).expect("server failed");
+        return;
+    }
+
+    // We assume we get only one remaining argument, the input file.
     assert_eq!(args.len(), 2);
     let input_path = args.remove(1);
 
     // Unrecoverable errors are bubbled up to here, where we panic.
-    run_engine(File::open(input_path).unwrap(), std::io::stdout()).expect("Unrecoverable error");
+    run_engine(
+        File::open(input_path).unwrap(),
+        std::io::stdout(),
+        strict,
+        workers,
+    )
+    .expect("Unrecoverable error");
 }
 
 #[cfg(test)]
@@ -103,7 +277,7 @@ mod tests {
         engine.process_from_csv(input).unwrap();
 
         let mut output = vec![];
-        for (client_id, account) in engine.0.iter() {
+        for (client_id, account) in engine.accounts.iter() {
             output.push(account.get_status(*client_id));
         }
 
@@ -226,7 +400,8 @@ mod tests {
             ],
         );
 
-        // verify that disputing a withdrawal doesn't work
+        // disputing a withdrawal rolls it back: available goes up by the withdrawn
+        // amount and held goes negative, total is unaffected.
         validate(
             r#"
                 type, client, tx, amount
@@ -236,8 +411,8 @@ mod tests {
                 .as_bytes(),
             vec![AccountStatus {
                 client: 1,
-                available: dec!(1.5),
-                held: dec!(0.0),
+                available: dec!(3.5),
+                held: dec!(-2.0),
                 total: dec!(1.5),
                 locked: false,
             }],
@@ -382,5 +557,156 @@ mod tests {
                 },
             ],
         );
+
+        // Chargeback of a disputed withdrawal: the withdrawal never happened, so
+        // available keeps the rolled-back funds and held returns to zero.
+        validate(
+            r#"
+                type, client, tx, amount
+                deposit, 1, 1, 5
+                withdrawal, 1, 2, 2
+                dispute, 1, 2,
+                chargeback, 1, 2,"#
+                .as_bytes(),
+            vec![AccountStatus {
+                client: 1,
+                available: dec!(5.0),
+                held: dec!(0.0),
+                total: dec!(5.0),
+                locked: true,
+            }],
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_collects_errors() {
+        use crate::error::LedgerError;
+
+        let mut engine = Engine::default().strict();
+        engine
+            .process_from_csv(
+                r#"
+                type, client, tx, amount
+                deposit, 1, 1, 3.0
+                withdrawal, 1, 2, 10.0
+                dispute, 1, 99,"#
+                    .as_bytes(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            engine.errors(),
+            &[
+                (3, LedgerError::NotEnoughFunds),
+                (
+                    4,
+                    LedgerError::UnknownTransaction { client: 1, tx: 99 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_workers_keeps_partial_results_on_read_error() {
+        // A malformed row partway through a CSV file.
+        let input = r#"
+            type, client, tx, amount
+            deposit, 1, 1, 5.0
+            bogus, 1, 2, 1.0"#;
+
+        let mut sequential: Engine = Default::default();
+        assert!(sequential.process_from_csv(input.as_bytes()).is_err());
+
+        let mut parallel = Engine::default().with_workers(2);
+        assert!(parallel.process_from_csv(input.as_bytes()).is_err());
+
+        // The sharded path should keep whatever it already merged before the
+        // read error, just like the sequential path keeps what it already
+        // applied to `self`.
+        assert_eq!(parallel.accounts.len(), sequential.accounts.len());
+        assert_eq!(parallel.accounts.len(), 1);
+    }
+
+    #[test]
+    fn test_write_status_to_csv_orders_by_client_and_rounds() {
+        let mut engine: Engine = Default::default();
+        engine
+            .process_from_csv(
+                r#"
+                type, client, tx, amount
+                deposit, 3, 1, 1.0
+                deposit, 1, 2, 2.0
+                deposit, 2, 3, 1.123456789"#
+                    .as_bytes(),
+            )
+            .unwrap();
+
+        let mut output = Vec::new();
+        engine.write_status_to_csv(&mut output, 4).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let rows: Vec<&str> = output.lines().skip(1).collect();
+
+        // Output is ordered by ascending client id, regardless of the order
+        // accounts were created in (and thus regardless of HashMap iteration order).
+        let client_ids: Vec<&str> = rows
+            .iter()
+            .map(|row| row.split(',').next().unwrap())
+            .collect();
+        assert_eq!(client_ids, vec!["1", "2", "3"]);
+
+        // Amounts are rounded to the requested scale.
+        assert!(rows[1].starts_with("2,1.1235,"));
+    }
+
+    #[test]
+    fn test_rounded_status_keeps_total_consistent() {
+        // Rounding `available` and `held` independently would give
+        // available=0.0000, held=0.0000, total=0.0001 -- rounding must instead
+        // derive `total` from the already-rounded components.
+        let status = AccountStatus {
+            client: 1,
+            available: dec!(0.00005),
+            held: dec!(0.00005),
+            total: dec!(0.00010),
+            locked: false,
+        }
+        .rounded(4);
+
+        assert_eq!(status.total, status.available + status.held);
+    }
+
+    #[test]
+    fn test_with_workers_matches_single_threaded() {
+        let input = r#"
+            type, client, tx, amount
+            deposit, 1, 1, 1.0
+            deposit, 2, 2, 2.0
+            deposit, 3, 3, 3.0
+            withdrawal, 1, 4, 0.5
+            withdrawal, 2, 5, 1.0
+            dispute, 3, 3,
+            deposit, 4, 6, 4.0"#;
+
+        let mut sequential: Engine = Default::default();
+        sequential.process_from_csv(input.as_bytes()).unwrap();
+
+        let mut parallel = Engine::default().with_workers(3);
+        parallel.process_from_csv(input.as_bytes()).unwrap();
+
+        let mut sequential_out: Vec<_> = sequential
+            .accounts
+            .iter()
+            .map(|(client_id, account)| account.get_status(*client_id))
+            .collect();
+        let mut parallel_out: Vec<_> = parallel
+            .accounts
+            .iter()
+            .map(|(client_id, account)| account.get_status(*client_id))
+            .collect();
+
+        sequential_out.sort_by_key(|line| line.client);
+        parallel_out.sort_by_key(|line| line.client);
+
+        assert_eq!(parallel_out, sequential_out);
     }
 }
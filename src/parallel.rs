@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+
+use csv::Error as CsvError;
+
+use crate::account::Account;
+use crate::apply_transaction;
+use crate::error::LedgerError;
+use crate::io::InputLine;
+use crate::ClientId;
+
+/// An input line tagged with the file line it came from, so errors can still be
+/// attributed once collected back on the main thread.
+struct Routed {
+    line: usize,
+    input_line: InputLine,
+}
+
+/// Per-shard state owned by a single worker thread: a disjoint slice of client
+/// accounts, partitioned by `ClientId % worker_count`, plus whatever errors were
+/// collected while applying transactions to it.
+#[derive(Default)]
+struct Shard {
+    accounts: HashMap<ClientId, Account>,
+    errors: Vec<(usize, LedgerError)>,
+}
+
+/// The merged account map and, in strict mode, every collected error.
+type Merged = (HashMap<ClientId, Account>, Vec<(usize, LedgerError)>);
+
+/// A CSV read error, alongside whatever was already merged from the worker
+/// shards before the read failed -- mirrors the sequential path, which keeps
+/// everything applied so far when it hits a read error partway through.
+type PartialFailure = (CsvError, Merged);
+
+/// Process `input_lines` across `worker_count` threads. Each client's
+/// transactions are always routed to the same worker (`ClientId % worker_count`)
+/// over a dedicated channel, so per-client ordering is preserved; ordering
+/// between different clients is not. Returns the merged account map and, in
+/// strict mode, every collected error. On a read error, still returns
+/// whatever was merged so far alongside the error.
+pub(crate) fn process<I>(
+    input_lines: I,
+    worker_count: usize,
+    strict: bool,
+) -> Result<Merged, PartialFailure>
+where
+    I: IntoIterator<Item = Result<(usize, InputLine), CsvError>>,
+{
+    let worker_count = worker_count.max(1);
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..worker_count)
+        .map(|_| mpsc::channel::<Routed>())
+        .unzip();
+
+    let handles: Vec<_> = receivers
+        .into_iter()
+        .map(|receiver| {
+            thread::spawn(move || {
+                let mut shard = Shard::default();
+                for routed in receiver {
+                    if let Err(error) = apply_transaction(&mut shard.accounts, routed.input_line) {
+                        if strict {
+                            shard.errors.push((routed.line, error));
+                        }
+                    }
+                }
+                shard
+            })
+        })
+        .collect();
+
+    let mut read_error = None;
+    for result in input_lines {
+        match result {
+            Ok((line, input_line)) => {
+                let shard = input_line.client as usize % worker_count;
+                senders[shard]
+                    .send(Routed { line, input_line })
+                    .expect("shard worker exited before the reader finished");
+            }
+            Err(error) => {
+                read_error = Some(error);
+                break;
+            }
+        }
+    }
+    // Dropping every sender lets each worker's `for routed in receiver` loop end.
+    drop(senders);
+
+    let mut accounts = HashMap::new();
+    let mut errors = Vec::new();
+    for handle in handles {
+        let shard = handle.join().expect("shard worker thread panicked");
+        accounts.extend(shard.accounts);
+        errors.extend(shard.errors);
+    }
+
+    match read_error {
+        Some(error) => Err((error, (accounts, errors))),
+        None => Ok((accounts, errors)),
+    }
+}
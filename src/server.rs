@@ -0,0 +1,39 @@
+use std::net::{TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::source::{JsonLinesSource, TransactionSource};
+use crate::Engine;
+
+/// Accept TCP connections on `addr` forever, each on its own thread reading a
+/// newline-delimited JSON transaction stream (see `source::JsonLinesSource`).
+/// Decoding a line never holds `engine`'s lock -- only applying the decoded
+/// transaction does -- so one slow or idle connection can't stall the others.
+pub(crate) fn serve<A: ToSocketAddrs>(addr: A, engine: Arc<Mutex<Engine>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let engine = Arc::clone(&engine);
+
+        thread::spawn(move || {
+            let mut source = JsonLinesSource::new(stream);
+            while let Some(result) = source.next_transaction() {
+                match result {
+                    Ok((line, input_line)) => {
+                        engine
+                            .lock()
+                            .expect("engine mutex poisoned")
+                            .process_one(line, input_line);
+                    }
+                    Err(error) => {
+                        eprintln!("connection dropped: {error}");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
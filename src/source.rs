@@ -0,0 +1,110 @@
+use std::io::BufRead;
+
+use csv::{Error as CsvError, Trim};
+use thiserror::Error;
+
+use crate::io::InputLine;
+
+/// A stream of transactions in some wire format, decoupling `Engine` from how
+/// a transaction was actually encoded. `CsvSource` and `JsonLinesSource` are
+/// the two implementations today; a TCP connection is just a `JsonLinesSource`
+/// over a `TcpStream` (see the `server` module). Each transaction comes back
+/// tagged with the line it was read from, in whatever numbering makes sense
+/// for that format (e.g. `CsvSource` accounts for the header row).
+pub(crate) trait TransactionSource {
+    type Error;
+
+    /// Pull the next transaction and the line it came from, or `None` once
+    /// the stream is exhausted.
+    fn next_transaction(&mut self) -> Option<Result<(usize, InputLine), Self::Error>>;
+}
+
+/// Reads transactions from a CSV reader, one data row at a time.
+pub(crate) struct CsvSource<R> {
+    reader: csv::Reader<R>,
+    /// The file line the next data row is on. Starts at 2: line 1 is the header.
+    next_line: usize,
+}
+
+impl<R: std::io::Read> CsvSource<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .trim(Trim::All)
+            .from_reader(reader);
+
+        Self {
+            reader,
+            next_line: 2,
+        }
+    }
+}
+
+impl<R: std::io::Read> TransactionSource for CsvSource<R> {
+    type Error = CsvError;
+
+    fn next_transaction(&mut self) -> Option<Result<(usize, InputLine), Self::Error>> {
+        let line = self.next_line;
+        self.next_line += 1;
+
+        self.reader
+            .deserialize::<InputLine>()
+            .next()
+            .map(|result| result.map(|input_line| (line, input_line)))
+    }
+}
+
+#[derive(Error, Debug)]
+/// Everything that can go wrong while reading a `JsonLinesSource`.
+pub(crate) enum JsonSourceError {
+    #[error("I/O error reading the transaction stream: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("malformed JSON transaction: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Reads transactions from newline-delimited JSON objects, e.g.
+/// `{"type":"deposit","client":1,"tx":1,"amount":"1.0"}`. Blank lines are
+/// skipped. Amounts deserialize straight into `rust_decimal::Decimal`, so
+/// fixed-point precision is preserved just like the CSV path.
+pub(crate) struct JsonLinesSource<R> {
+    lines: std::io::Lines<std::io::BufReader<R>>,
+    /// The stream line the next `lines.next()` call will read. There's no
+    /// header to account for here, so this just counts from 1.
+    next_line: usize,
+}
+
+impl<R: std::io::Read> JsonLinesSource<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            lines: std::io::BufReader::new(reader).lines(),
+            next_line: 1,
+        }
+    }
+}
+
+impl<R: std::io::Read> TransactionSource for JsonLinesSource<R> {
+    type Error = JsonSourceError;
+
+    fn next_transaction(&mut self) -> Option<Result<(usize, InputLine), Self::Error>> {
+        loop {
+            let line_number = self.next_line;
+            self.next_line += 1;
+
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(error) => return Some(Err(JsonSourceError::Io(error))),
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return Some(
+                serde_json::from_str(&line)
+                    .map(|input_line| (line_number, input_line))
+                    .map_err(JsonSourceError::Json),
+            );
+        }
+    }
+}
@@ -1,19 +1,44 @@
 use rust_decimal::Decimal;
 
 #[derive(Clone, Copy)]
-// Only need to store deposits for the moment.
 pub enum TransactionType {
     Deposit,
+    Withdrawal,
 }
 
-/// A transaction stored in the account. Currently, only deposits are stored, since
-/// those are the only transactions that may be disputed.
-/// We store the transaction type for extensibility.
+impl TransactionType {
+    /// The sign applied to a transaction's amount when it's disputed. Disputing a
+    /// deposit moves `+amount` from available to held, as if the money never
+    /// arrived. Disputing a withdrawal moves `-amount`, as if the money never left
+    /// (available goes up, held goes negative) -- this is what it means to
+    /// temporarily roll back a withdrawal pending investigation.
+    fn dispute_sign(&self) -> Decimal {
+        match self {
+            TransactionType::Deposit => Decimal::ONE,
+            TransactionType::Withdrawal => -Decimal::ONE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// The lifecycle of a disputable transaction. A transaction starts out `Processed`
+/// and may move to `Disputed`, from which it resolves one way or the other. Once a
+/// transaction is `Resolved` or `ChargedBack` it is done: it can never be disputed
+/// again, so a `ChargedBack` transaction cannot come back to life even if the account
+/// lock were ever lifted.
+pub enum TxState {
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+/// A transaction stored in the account. Both deposits and withdrawals are stored,
+/// since either may be disputed.
 pub struct Transaction {
-    #[allow(unused)]
     r#type: TransactionType,
     amount: Decimal,
-    disputed: bool,
+    state: TxState,
 }
 
 impl Transaction {
@@ -21,27 +46,49 @@ impl Transaction {
         Self {
             r#type: TransactionType::Deposit,
             amount,
-            disputed: false,
+            state: TxState::Processed,
+        }
+    }
+
+    pub fn withdrawal(amount: Decimal) -> Self {
+        Self {
+            r#type: TransactionType::Withdrawal,
+            amount,
+            state: TxState::Processed,
         }
     }
 
-    pub fn amount(&self) -> Decimal {
-        self.amount
+    /// The signed amount to apply when this transaction is disputed: positive for a
+    /// deposit, negative for a withdrawal. `resolve`/`chargeback` reuse the same
+    /// sign to unwind or finalize the dispute.
+    pub fn signed_amount(&self) -> Decimal {
+        self.amount * self.r#type.dispute_sign()
     }
 
-    pub fn dispute(&mut self) {
-        if !self.disputed {
-            self.disputed = true;
-        }
+    /// Move this transaction into the `Disputed` state. Only legal from `Processed`;
+    /// any other starting state is a no-op.
+    pub fn dispute(&mut self) -> bool {
+        self.transition(TxState::Processed, TxState::Disputed)
     }
 
-    pub fn undispute(&mut self) {
-        if self.disputed {
-            self.disputed = false;
-        }
+    /// Move this transaction into the `Resolved` state. Only legal from `Disputed`.
+    pub fn resolve(&mut self) -> bool {
+        self.transition(TxState::Disputed, TxState::Resolved)
+    }
+
+    /// Move this transaction into the `ChargedBack` state. Only legal from `Disputed`.
+    pub fn chargeback(&mut self) -> bool {
+        self.transition(TxState::Disputed, TxState::ChargedBack)
     }
 
-    pub fn disputed(&self) -> bool {
-        self.disputed
+    /// Move from `from` to `to` if we're currently in `from`. Returns whether the
+    /// transition happened, so callers can gate their own side effects on it.
+    fn transition(&mut self, from: TxState, to: TxState) -> bool {
+        if self.state == from {
+            self.state = to;
+            true
+        } else {
+            false
+        }
     }
 }